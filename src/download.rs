@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+use sha1::{Digest, Sha1};
+
+use crate::message::Message;
+use crate::torrentfile::{Info, BLOCK_SIZE};
+
+const MAX_PIECE_ATTEMPTS: usize = 5;
+
+/// A connected peer together with the BEP3 choke state the engine has
+/// negotiated with it so far. Choke state is per-connection and persists
+/// across pieces: a peer only sends `Unchoke` once, so we must remember
+/// we're already unchoked instead of waiting for it again on every piece.
+pub struct PeerConnection {
+    stream: TcpStream,
+    choked: bool,
+}
+
+impl PeerConnection {
+    pub fn new(stream: TcpStream) -> Self {
+        PeerConnection {
+            stream,
+            choked: true,
+        }
+    }
+}
+
+/// Downloads every piece of `info` from the connected `streams` and writes
+/// the result under `output_dir`, verifying each piece's SHA1 before it's
+/// written to disk.
+pub fn download(
+    info: &Info,
+    streams: &mut HashMap<String, PeerConnection>,
+    output_dir: &Path,
+) -> Result<(), String> {
+    for index in 0..info.piece_count() {
+        let mut piece = None;
+        let mut last_err = String::new();
+
+        for _ in 0..MAX_PIECE_ATTEMPTS {
+            match download_piece(info, index, streams) {
+                Ok(buf) => {
+                    piece = Some(buf);
+                    break;
+                }
+                Err(err) => last_err = err,
+            }
+        }
+
+        let piece = match piece {
+            Some(piece) => piece,
+            None => {
+                return Err(format!(
+                    "Failed downloading piece {} after {} attempts: {}",
+                    index, MAX_PIECE_ATTEMPTS, last_err
+                ))
+            }
+        };
+
+        write_piece(info, output_dir, index, &piece)?;
+        println!("Downloaded piece {}/{}", index + 1, info.piece_count());
+    }
+
+    Ok(())
+}
+
+/// Tries every connected peer in turn until one of them serves a verified copy of `index`.
+fn download_piece(
+    info: &Info,
+    index: usize,
+    streams: &mut HashMap<String, PeerConnection>,
+) -> Result<Vec<u8>, String> {
+    for (address, peer) in streams.iter_mut() {
+        match download_piece_from_peer(peer, info, index) {
+            Ok(buf) => return Ok(buf),
+            Err(err) => println!("Peer {} failed serving piece {}: {}", address, index, err),
+        }
+    }
+    Err(String::from("No peer could serve this piece"))
+}
+
+fn download_piece_from_peer(
+    peer: &mut PeerConnection,
+    info: &Info,
+    index: usize,
+) -> Result<Vec<u8>, String> {
+    send_message(&mut peer.stream, &Message::Interested)?;
+
+    while peer.choked {
+        match Message::read(&mut peer.stream)? {
+            Some(Message::Unchoke) => peer.choked = false,
+            Some(_) | None => continue,
+        }
+    }
+
+    let blocks = info.blocks_per_piece(index);
+    for block in 0..blocks {
+        let begin = (block * BLOCK_SIZE) as u32;
+        let length = info.block_len(index, block) as u32;
+        send_message(
+            &mut peer.stream,
+            &Message::Request {
+                index: index as u32,
+                begin,
+                length,
+            },
+        )?;
+    }
+
+    let mut buf = vec![0u8; info.piece_len(index)];
+    let mut remaining = blocks;
+    while remaining > 0 {
+        match Message::read(&mut peer.stream)? {
+            Some(Message::Piece {
+                index: piece_index,
+                begin,
+                block,
+            }) => {
+                if piece_index as usize != index {
+                    continue;
+                }
+                let start = begin as usize;
+                let end = match start.checked_add(block.len()) {
+                    Some(end) if end <= buf.len() => end,
+                    _ => continue,
+                };
+                buf[start..end].copy_from_slice(&block);
+                remaining -= 1;
+            }
+            Some(Message::Choke) => {
+                peer.choked = true;
+                return Err(String::from("Peer choked us mid-piece"));
+            }
+            Some(_) | None => continue,
+        }
+    }
+
+    let expected = &info.pieces[index * 20..index * 20 + 20];
+    if Sha1::digest(&buf).as_slice() != expected {
+        return Err(format!("SHA1 mismatch for piece {}", index));
+    }
+
+    Ok(buf)
+}
+
+fn send_message(stream: &mut TcpStream, message: &Message) -> Result<(), String> {
+    stream
+        .write_all(&message.to_bytes())
+        .map_err(|err| format!("Failed sending message: {}", err))
+}
+
+/// Writes a verified piece to disk at `index * piece_length`, splitting it
+/// across file boundaries when `info.files` describes a multi-file torrent.
+fn write_piece(info: &Info, output_dir: &Path, index: usize, data: &[u8]) -> Result<(), String> {
+    let piece_offset = index * info.piece_length;
+
+    match &info.files {
+        None => write_at(&output_dir.join(&info.name), piece_offset, data),
+        Some(files) => {
+            let mut file_start = 0usize;
+            let mut remaining = data;
+            let mut write_offset = piece_offset;
+
+            for file in files {
+                let file_end = file_start + file.length;
+
+                if write_offset < file_end && !remaining.is_empty() {
+                    let in_file_offset = write_offset - file_start;
+                    let available = file_end - file_start - in_file_offset;
+                    let chunk_len = remaining.len().min(available);
+
+                    let mut path = output_dir.join(&info.name);
+                    path.extend(file.path.iter().map(PathBuf::from));
+                    write_at(&path, in_file_offset, &remaining[..chunk_len])?;
+
+                    remaining = &remaining[chunk_len..];
+                    write_offset += chunk_len;
+                }
+
+                file_start = file_end;
+                if remaining.is_empty() {
+                    break;
+                }
+            }
+
+            Ok(())
+        }
+    }
+}
+
+fn write_at(path: &Path, offset: usize, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|err| format!("Failed creating directory {}: {}", parent.display(), err))?;
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(path)
+        .map_err(|err| format!("Failed opening {}: {}", path.display(), err))?;
+
+    file.seek(SeekFrom::Start(offset as u64))
+        .map_err(|err| format!("Failed seeking in {}: {}", path.display(), err))?;
+
+    file.write_all(data)
+        .map_err(|err| format!("Failed writing to {}: {}", path.display(), err))
+}
@@ -1,37 +1,79 @@
 use core::panic;
 
+pub mod download;
 pub mod message;
 pub mod peer_id;
 pub mod torrentfile;
+pub mod udp_tracker;
 
+use crate::download::PeerConnection;
 use crate::message::{Handshake, Peer, TrackerResponse};
 use crate::peer_id::generate_peer_id;
 use crate::torrentfile::MetaInfo;
 
-use std::{
-    collections::HashMap,
-    io::{Read, Write},
-    net::TcpStream,
-    path::PathBuf,
-};
+use std::{collections::HashMap, net::TcpStream, path::PathBuf, time::Duration};
 
-use bincode::Options;
 use clap::Parser;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream as TokioTcpStream;
+
+/// How long we're willing to wait for a single peer handshake before giving up on it.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long a `read` on an established peer connection may block before we
+/// give up on that peer and let the piece-download retry loop move on.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Size in bytes of the BEP3 handshake message: 1 (pstrlen) + 19 (pstr) + 8
+/// (reserved) + 20 (info_hash) + 20 (peer_id).
+const HANDSHAKE_LEN: usize = 68;
+
+async fn tracker_get(torrent: &mut MetaInfo, peer_id: &String) -> Result<TrackerResponse, String> {
+    let mut tiers = match torrent.announce_list.take() {
+        Some(tiers) => tiers,
+        None => return tracker_get_one(torrent, &torrent.announce.clone(), peer_id).await,
+    };
+
+    let mut result = Err(String::from("No tracker in announce-list answered"));
+    'tiers: for tier in tiers.iter_mut() {
+        for i in 0..tier.len() {
+            match tracker_get_one(torrent, &tier[i], peer_id).await {
+                Ok(res) => {
+                    let tracker = tier.remove(i);
+                    tier.insert(0, tracker);
+                    result = Ok(res);
+                    break 'tiers;
+                }
+                Err(err) => result = Err(err),
+            }
+        }
+    }
+
+    torrent.announce_list = Some(tiers);
+    result
+}
 
-fn tracker_get(torrent: &MetaInfo, peer_id: &String) -> Result<TrackerResponse, String> {
-    if !torrent.announce.starts_with("http") {
-        // TODO: Support UDP trackers
-        let protocol = torrent.announce.split(":").collect::<Vec<&str>>()[0];
-        panic!("{} trackers not supported", protocol)
+async fn tracker_get_one(
+    torrent: &MetaInfo,
+    tracker_url: &str,
+    peer_id: &String,
+) -> Result<TrackerResponse, String> {
+    if tracker_url.starts_with("udp") {
+        let peer_id = udp_tracker::percent_decode(peer_id);
+        return udp_tracker::tracker_get(tracker_url, &torrent.info.hash(), &peer_id, torrent.info.size())
+            .await;
     }
-    let url = torrent.build_tracker_url(&peer_id);
-    let client = reqwest::blocking::Client::new();
-    let res = match client.get(url).send() {
+    if !tracker_url.starts_with("http") {
+        let protocol = tracker_url.split(":").collect::<Vec<&str>>()[0];
+        return Err(format!("{} trackers not supported", protocol));
+    }
+    let url = torrent.build_tracker_url(tracker_url, &peer_id);
+    let client = reqwest::Client::new();
+    let res = match client.get(url).send().await {
         Ok(res) => res,
         Err(err) => return Err(format!("Failed request: {}", err)),
     };
 
-    let body = match res.bytes() {
+    let body = match res.bytes().await {
         Ok(body) => body,
         Err(err) => return Err(format!("Failed reading response body: {}", err)),
     };
@@ -49,73 +91,65 @@ fn tracker_get(torrent: &MetaInfo, peer_id: &String) -> Result<TrackerResponse,
     Ok(tracker_res)
 }
 
-fn open_stream(peer: &Peer, info_hash: &String, peer_id: &String) -> Result<TcpStream, String> {
-    // let timeout = Duration::new(3, 0);
-    // let mut stream = match TcpStream::connect_timeout(peer.address(), timeout) {
-    let mut stream = match TcpStream::connect(peer.address()) {
+async fn open_stream(peer: &Peer, info_hash: &[u8; 20], peer_id: &String) -> Result<TcpStream, String> {
+    let mut stream = match TokioTcpStream::connect(peer.address()).await {
         Ok(s) => s,
         Err(err) => return Err(format!("Failed opening TCP stream: {}", err)),
     };
 
     // Handshake
-    let handshake = Handshake::new(info_hash, peer_id);
-
-    // let mut data: Vec<u8> = vec![];
-    // data.push(handshake.protocol_string_length);
-    // data.extend_from_slice(handshake.protocol.as_bytes());
-    // data.extend_from_slice(&handshake.reserved[..]);
-    // data.extend_from_slice(handshake.info_hash.as_bytes());
-    // data.extend_from_slice(handshake.peer_id.as_bytes());
-
-    let options = bincode::DefaultOptions::new();
-    // .with_big_endian()
-    // .allow_trailing_bytes()
-    // .with_fixint_encoding();
-
-    let handshake = match options.serialize(&handshake) {
-        Ok(b) => b,
-        Err(err) => return Err(format!("Failed serializing handshake: {}", err)),
-    };
+    let handshake = Handshake::new(&info_hash.to_vec(), peer_id);
+    let handshake = handshake.to_bytes();
 
-    match stream.write(&handshake) {
-        Ok(size) => println!("Sent {} bytes", size),
+    match stream.write_all(&handshake).await {
+        Ok(()) => println!("Sent {} bytes", handshake.len()),
         Err(err) => return Err(format!("Failed sending handshake: {}", err)),
     };
 
-    // We're using 68 as the size here cause we know the exact length of the handshake message, that is:
-    // protocol_string_length: 1 bytes
-    // protocol: 19 bytes
-    // reserved: 8 bytes
-    // info_hash: 20 bytes
-    // peer_id: 20 bytes
-    // This is not completely reliable as we relying on the fact the protocol is string is exactly
-    // 19 bytes long, but it could not be. This is good enough for the time being.
-    let mut buf = [0; 300];
-
-    match stream.read(&mut buf[..]) {
-        Ok(size) => println!("Received {} bytes", size),
+    // Read exactly one handshake's worth of bytes. Peers routinely pipeline
+    // `Bitfield`/`Have` messages right after the handshake, so reading more
+    // than `HANDSHAKE_LEN` here would consume and drop the start of that
+    // stream, desyncing the length-prefixed framing `Message::read` relies on.
+    let mut buf = [0; HANDSHAKE_LEN];
+
+    match stream.read_exact(&mut buf).await {
+        Ok(_) => {}
         Err(err) => return Err(format!("Failed reading data from peer: {}", err)),
     };
 
-    println!("sent     bytes: {:?}", handshake);
-    println!("received bytes: {:?}", buf);
-
-    let handshake: Handshake = match options.deserialize(&buf) {
+    let handshake = match Handshake::from_bytes(&buf) {
         Ok(res) => res,
         Err(err) => return Err(format!("Failed deserializing handshake: {}", err)),
     };
-    if handshake.info_hash != info_hash {
+    if handshake.info_hash.as_slice() != info_hash {
         // This is not the file we want, there's something wrong.
         // Close the connection.
-        _ = stream.shutdown(std::net::Shutdown::Both);
+        _ = stream.shutdown().await;
         return Err(String::from("Received wrong info hash from peer"));
     }
     if peer_id.len() > 0 && peer_id != handshake.peer_id {
         // This peer is returning a different id than expected.
         // Close the connection.
-        _ = stream.shutdown(std::net::Shutdown::Both);
+        _ = stream.shutdown().await;
         return Err(String::from("Received unexpected peer id from peer"));
     }
+
+    // The rest of the client (the download engine) talks to peers over
+    // blocking std sockets, so hand the stream back over once the async
+    // connect-and-handshake dance is done.
+    let stream = match stream.into_std() {
+        Ok(s) => s,
+        Err(err) => return Err(format!("Failed converting stream: {}", err)),
+    };
+    if let Err(err) = stream.set_nonblocking(false) {
+        return Err(format!("Failed configuring stream: {}", err));
+    }
+    // Bound reads on the download engine's blocking socket so a peer that
+    // accepts the connection but goes silent mid-piece gets abandoned
+    // instead of stalling the whole download.
+    if let Err(err) = stream.set_read_timeout(Some(READ_TIMEOUT)) {
+        return Err(format!("Failed configuring stream: {}", err));
+    }
     Ok(stream)
 }
 
@@ -124,7 +158,8 @@ struct Cli {
     torrent_file: PathBuf,
 }
 
-fn main() {
+#[tokio::main]
+async fn main() {
     let args = Cli::parse();
     let torrent_file = args.torrent_file;
 
@@ -139,7 +174,7 @@ fn main() {
     };
 
     // Deserialize torrent data
-    let res = match serde_bencode::from_bytes::<MetaInfo>(&buf.as_slice()) {
+    let mut res = match serde_bencode::from_bytes::<MetaInfo>(&buf.as_slice()) {
         Ok(res) => res,
         Err(err) => {
             panic!("{}", err);
@@ -149,6 +184,7 @@ fn main() {
     // Print torrent info
     println!("{}", res.info.name);
     println!("Tracker: {}", res.announce);
+    println!("Info hash: {}", res.info.hash_hex());
     println!("Piece length: {}", res.info.piece_length);
 
     println!("Number of pieces: {}", res.info.pieces.chunks(20).len());
@@ -164,23 +200,48 @@ fn main() {
         }
     }
 
-    let tracker_response = match tracker_get(&res, &peer_id) {
+    let tracker_response = match tracker_get(&mut res, &peer_id).await {
         Ok(res) => res,
         Err(err) => panic!("{}", err),
     };
 
-    let mut streams = HashMap::new();
+    let info_hash = res.info.hash();
+
+    let mut tasks = tokio::task::JoinSet::new();
     for peer in tracker_response.peers {
-        println!("Trying to connect to {}", peer.address());
-        let stream = match open_stream(&peer, &res.info.hash(), &peer_id) {
-            Ok(s) => s,
-            Err(err) => {
-                println!("Couldn't connect with peer: {:?}", err);
-                continue;
+        let peer_id = peer_id.clone();
+        tasks.spawn(async move {
+            let address = peer.address();
+            println!("Trying to connect to {}", address);
+
+            match tokio::time::timeout(CONNECT_TIMEOUT, open_stream(&peer, &info_hash, &peer_id)).await {
+                Ok(Ok(stream)) => Some((address, stream)),
+                Ok(Err(err)) => {
+                    println!("Couldn't connect with peer: {:?}", err);
+                    None
+                }
+                Err(_) => {
+                    println!("Timed out connecting to {}", address);
+                    None
+                }
+            }
+        });
+    }
+
+    let mut streams = HashMap::new();
+    while let Some(result) = tasks.join_next().await {
+        match result {
+            Ok(Some((address, stream))) => {
+                println!("Opened stream with {}", address);
+                streams.insert(address, PeerConnection::new(stream));
             }
-        };
-        streams.insert(peer.address(), stream);
+            Ok(None) => {}
+            Err(err) => println!("Peer connection task panicked: {}", err),
+        }
+    }
 
-        println!("Opened stream with {}", peer.address());
+    match download::download(&res.info, &mut streams, &PathBuf::from(".")) {
+        Ok(()) => println!("Download complete"),
+        Err(err) => panic!("{}", err),
     }
 }
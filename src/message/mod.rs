@@ -1,7 +1,9 @@
 pub mod handshake;
 pub mod peer;
 pub mod tracker_response;
+pub mod wire;
 
 pub use handshake::Handshake;
 pub use peer::Peer;
 pub use tracker_response::TrackerResponse;
+pub use wire::Message;
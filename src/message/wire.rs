@@ -0,0 +1,133 @@
+use std::io::Read;
+use std::net::TcpStream;
+
+/// A peer wire protocol message as defined in BEP3.
+/// https://www.bittorrent.org/beps/bep_0003.html#peer-messages
+///
+/// On the wire every message is `<length prefix><message id><payload>`,
+/// where `length prefix` is a 4 byte big-endian integer giving the length
+/// of the rest of the message. A length prefix of zero with no id or
+/// payload is a keep-alive.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Choke,
+    Unchoke,
+    Interested,
+    NotInterested,
+    Have(u32),
+    Bitfield(Vec<u8>),
+    Request { index: u32, begin: u32, length: u32 },
+    Piece { index: u32, begin: u32, block: Vec<u8> },
+    Cancel { index: u32, begin: u32, length: u32 },
+}
+
+impl Message {
+    fn id(&self) -> u8 {
+        match self {
+            Message::Choke => 0,
+            Message::Unchoke => 1,
+            Message::Interested => 2,
+            Message::NotInterested => 3,
+            Message::Have(_) => 4,
+            Message::Bitfield(_) => 5,
+            Message::Request { .. } => 6,
+            Message::Piece { .. } => 7,
+            Message::Cancel { .. } => 8,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = vec![];
+        match self {
+            Message::Choke | Message::Unchoke | Message::Interested | Message::NotInterested => {}
+            Message::Have(piece_index) => payload.extend_from_slice(&piece_index.to_be_bytes()),
+            Message::Bitfield(bitfield) => payload.extend_from_slice(bitfield),
+            Message::Request { index, begin, length }
+            | Message::Cancel { index, begin, length } => {
+                payload.extend_from_slice(&index.to_be_bytes());
+                payload.extend_from_slice(&begin.to_be_bytes());
+                payload.extend_from_slice(&length.to_be_bytes());
+            }
+            Message::Piece { index, begin, block } => {
+                payload.extend_from_slice(&index.to_be_bytes());
+                payload.extend_from_slice(&begin.to_be_bytes());
+                payload.extend_from_slice(block);
+            }
+        }
+
+        let length = (payload.len() + 1) as u32;
+        let mut data = Vec::with_capacity(4 + payload.len() + 1);
+        data.extend_from_slice(&length.to_be_bytes());
+        data.push(self.id());
+        data.extend(payload);
+        data
+    }
+
+    /// Reads a single message off `stream`, returning `Ok(None)` for a
+    /// keep-alive (a zero length prefix with no id or payload).
+    pub fn read(stream: &mut TcpStream) -> Result<Option<Message>, String> {
+        let mut length_buf = [0u8; 4];
+        if let Err(err) = stream.read_exact(&mut length_buf) {
+            return Err(format!("Failed reading message length: {}", err));
+        }
+        let length = u32::from_be_bytes(length_buf);
+        if length == 0 {
+            return Ok(None);
+        }
+
+        let mut body = vec![0u8; length as usize];
+        if let Err(err) = stream.read_exact(&mut body) {
+            return Err(format!("Failed reading message body: {}", err));
+        }
+
+        let id = body[0];
+        let payload = &body[1..];
+
+        let message = match id {
+            0 => Message::Choke,
+            1 => Message::Unchoke,
+            2 => Message::Interested,
+            3 => Message::NotInterested,
+            4 => {
+                if payload.len() < 4 {
+                    return Err(String::from("Have message payload too short"));
+                }
+                Message::Have(u32::from_be_bytes(payload[0..4].try_into().unwrap()))
+            }
+            5 => Message::Bitfield(payload.to_vec()),
+            6 => {
+                if payload.len() < 12 {
+                    return Err(String::from("Request message payload too short"));
+                }
+                Message::Request {
+                    index: u32::from_be_bytes(payload[0..4].try_into().unwrap()),
+                    begin: u32::from_be_bytes(payload[4..8].try_into().unwrap()),
+                    length: u32::from_be_bytes(payload[8..12].try_into().unwrap()),
+                }
+            }
+            7 => {
+                if payload.len() < 8 {
+                    return Err(String::from("Piece message payload too short"));
+                }
+                Message::Piece {
+                    index: u32::from_be_bytes(payload[0..4].try_into().unwrap()),
+                    begin: u32::from_be_bytes(payload[4..8].try_into().unwrap()),
+                    block: payload[8..].to_vec(),
+                }
+            }
+            8 => {
+                if payload.len() < 12 {
+                    return Err(String::from("Cancel message payload too short"));
+                }
+                Message::Cancel {
+                    index: u32::from_be_bytes(payload[0..4].try_into().unwrap()),
+                    begin: u32::from_be_bytes(payload[4..8].try_into().unwrap()),
+                    length: u32::from_be_bytes(payload[8..12].try_into().unwrap()),
+                }
+            }
+            _ => return Err(format!("Unknown message id: {}", id)),
+        };
+
+        Ok(Some(message))
+    }
+}
@@ -1,6 +1,10 @@
 use serde_derive::{Deserialize, Serialize};
 use sha1::{Digest, Sha1};
 
+/// Size in bytes of a single block requested from a peer, as used by the
+/// `Request`/`Piece` wire messages (2^14, the de-facto standard block size).
+pub const BLOCK_SIZE: usize = 1 << 14;
+
 /// Info represents the metadata found in the `info` field in a .torrent file
 /// as defined in BEP003.
 /// https://www.bittorrent.org/beps/bep_0003.html#info-dictionary
@@ -31,27 +35,58 @@ pub struct Info {
 }
 
 impl Info {
-    pub fn hash(&self) -> String {
+    /// Raw 20 byte SHA1 digest of the bencoded `info` dictionary, as used for
+    /// the `info_hash` sent to trackers and peers.
+    pub fn hash(&self) -> [u8; 20] {
         let buf = match serde_bencode::to_bytes(self) {
             Ok(buf) => buf,
             Err(err) => {
                 panic!("Failed parsing: {}", err)
             }
         };
-        Sha1::digest(buf).iter().map(|b| format!("{}", b)).collect()
+        Sha1::digest(buf).into()
     }
 
-    pub fn hash_encoded(&self) -> String {
-        let buf = match serde_bencode::to_bytes(self) {
-            Ok(buf) => buf,
-            Err(err) => {
-                panic!("Failed parsing: {}", err)
+    /// `hash()` as a lowercase hex string, for display.
+    pub fn hash_hex(&self) -> String {
+        self.hash().iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// Number of pieces the torrent is split into.
+    pub fn piece_count(&self) -> usize {
+        self.pieces.len() / 20
+    }
+
+    /// Length in bytes of the piece at `index`. Every piece has `piece_length`
+    /// bytes except the last one, which holds whatever remains of the total size.
+    pub fn piece_len(&self, index: usize) -> usize {
+        if index == self.piece_count() - 1 {
+            let remainder = self.size() % self.piece_length;
+            if remainder == 0 {
+                self.piece_length
+            } else {
+                remainder
             }
-        };
-        Sha1::digest(buf)
-            .iter()
-            .map(|b: &u8| format!("%{:02X}", b))
-            .collect()
+        } else {
+            self.piece_length
+        }
+    }
+
+    /// Number of `BLOCK_SIZE` blocks the piece at `index` is split into when requested from peers.
+    pub fn blocks_per_piece(&self, index: usize) -> usize {
+        (self.piece_len(index) + BLOCK_SIZE - 1) / BLOCK_SIZE
+    }
+
+    /// Length in bytes of `block` within the piece at `index`. Every block is
+    /// `BLOCK_SIZE` bytes except the last one in a piece, which holds the remainder.
+    pub fn block_len(&self, index: usize, block: usize) -> usize {
+        let piece_len = self.piece_len(index);
+        let start = block * BLOCK_SIZE;
+        if start + BLOCK_SIZE > piece_len {
+            piece_len - start
+        } else {
+            BLOCK_SIZE
+        }
     }
 
     pub fn size(&self) -> usize {
@@ -1,4 +1,3 @@
-use crate::message::TrackerResponse;
 use crate::torrentfile::Info;
 use serde_derive::{Deserialize, Serialize};
 
@@ -8,20 +7,36 @@ use serde_derive::{Deserialize, Serialize};
 pub struct MetaInfo {
     /// Tracker URL
     pub announce: String,
+    /// Tiered backup tracker list as defined in BEP012. Tiers are tried in
+    /// order and, within a tier, trackers are tried in order.
+    /// https://www.bittorrent.org/beps/bep_0012.html
+    #[serde(default, rename = "announce-list", skip_serializing_if = "Option::is_none")]
+    pub announce_list: Option<Vec<Vec<String>>>,
     /// Torrent metadata
     pub info: Info,
 }
 
+/// Percent-encodes `bytes` following the tracker convention: unreserved
+/// characters (`A-Z a-z 0-9 . - _ ~`) are emitted literally, everything else
+/// as `%XX`.
+fn percent_encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'.' | b'-' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
 impl MetaInfo {
-    pub fn build_tracker_url(&self, peer_id: &Vec<u8>) -> String {
+    pub fn build_tracker_url(&self, tracker_url: &str, peer_id: &Vec<u8>) -> String {
         format!(
             "{}?info_hash={}&peer_id={}&port={}&uploaded={}&downloaded={}&compact={}&left={}",
-            self.announce, // Tracker url
-            self.info
-                .hash()
-                .iter()
-                .map(|b| format!("%{:02X}", b))
-                .collect::<String>(), // info_hash
+            tracker_url,                        // Tracker url
+            percent_encode(&self.info.hash()), // info_hash
             peer_id
                 .iter()
                 .map(|b| format!("%{:02X}", b))
@@ -33,35 +48,4 @@ impl MetaInfo {
             self.info.size(), // left
         )
     }
-
-    pub fn tracker_get(&self, peer_id: &Vec<u8>) -> Result<TrackerResponse, String> {
-        if !self.announce.starts_with("http") {
-            // TODO: Support UDP trackers
-            let protocol = self.announce.split(":").collect::<Vec<&str>>()[0];
-            panic!("{} trackers not supported", protocol)
-        }
-        let url = self.build_tracker_url(&peer_id);
-        let client = reqwest::blocking::Client::new();
-        let res = match client.get(url).send() {
-            Ok(res) => res,
-            Err(err) => return Err(format!("Failed request: {}", err)),
-        };
-
-        let body = match res.bytes() {
-            Ok(body) => body,
-            Err(err) => return Err(format!("Failed reading response body: {}", err)),
-        };
-
-        let tracker_res = match serde_bencode::from_bytes::<TrackerResponse>(&body) {
-            Ok(tracker_res) => tracker_res,
-            Err(err) => return Err(format!("Failed parsing response body: {}", err)),
-        };
-
-        match tracker_res.failure_reason {
-            Some(err) => return Err(err),
-            _ => {}
-        }
-
-        Ok(tracker_res)
-    }
 }
@@ -0,0 +1,5 @@
+pub mod info;
+pub mod metainfo;
+
+pub use info::{File, Info, BLOCK_SIZE};
+pub use metainfo::MetaInfo;
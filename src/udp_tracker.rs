@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+
+use crate::message::{Peer, TrackerResponse};
+
+/// Magic constant used to identify the protocol as defined in BEP15.
+/// https://www.bittorrent.org/beps/bep_0015.html
+const PROTOCOL_ID: u64 = 0x41727101980;
+const ACTION_CONNECT: u32 = 0;
+const ACTION_ANNOUNCE: u32 = 1;
+const MAX_RETRIES: u32 = 8;
+
+/// Sends `request` and waits for a response, retrying with the BEP15 backoff
+/// schedule of `15 * 2^n` seconds (n = retry count) up to `MAX_RETRIES` tries.
+async fn send_with_retries(socket: &UdpSocket, request: &[u8], response: &mut [u8]) -> Result<usize, String> {
+    for retry in 0..MAX_RETRIES {
+        let timeout = Duration::from_secs(15 * 2u64.pow(retry));
+
+        if let Err(err) = socket.send(request).await {
+            return Err(format!("Failed sending UDP request: {}", err));
+        }
+
+        match tokio::time::timeout(timeout, socket.recv(response)).await {
+            Ok(Ok(size)) => return Ok(size),
+            Ok(Err(err)) => return Err(format!("Failed receiving UDP response: {}", err)),
+            Err(_) => continue,
+        }
+    }
+    Err(String::from("Tracker did not respond after maximum retries"))
+}
+
+/// Performs the connect handshake described in BEP15 and returns the
+/// `connection_id` to use for the following announce request.
+async fn connect(socket: &UdpSocket) -> Result<u64, String> {
+    let transaction_id: u32 = rand::random();
+
+    let mut request = Vec::with_capacity(16);
+    request.extend_from_slice(&PROTOCOL_ID.to_be_bytes());
+    request.extend_from_slice(&ACTION_CONNECT.to_be_bytes());
+    request.extend_from_slice(&transaction_id.to_be_bytes());
+
+    let mut response = [0u8; 16];
+    let size = send_with_retries(socket, &request, &mut response).await?;
+    if size < 16 {
+        return Err(String::from("Connect response from tracker was too short"));
+    }
+
+    let action = u32::from_be_bytes(response[0..4].try_into().unwrap());
+    let res_transaction_id = u32::from_be_bytes(response[4..8].try_into().unwrap());
+    if action != ACTION_CONNECT || res_transaction_id != transaction_id {
+        return Err(String::from("Received unexpected connect response from tracker"));
+    }
+
+    Ok(u64::from_be_bytes(response[8..16].try_into().unwrap()))
+}
+
+/// Announces to a UDP tracker (BEP15) and returns the same `TrackerResponse`
+/// the HTTP path produces.
+pub async fn tracker_get(
+    announce: &str,
+    info_hash: &[u8],
+    peer_id: &[u8],
+    left: usize,
+) -> Result<TrackerResponse, String> {
+    let address = announce
+        .trim_start_matches("udp://")
+        .trim_end_matches('/');
+
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(s) => s,
+        Err(err) => return Err(format!("Failed binding UDP socket: {}", err)),
+    };
+    if let Err(err) = socket.connect(address).await {
+        return Err(format!("Failed connecting to tracker {}: {}", address, err));
+    }
+
+    let connection_id = connect(&socket).await?;
+
+    let transaction_id: u32 = rand::random();
+    let key: u32 = rand::random();
+
+    let mut request = Vec::with_capacity(98);
+    request.extend_from_slice(&connection_id.to_be_bytes());
+    request.extend_from_slice(&ACTION_ANNOUNCE.to_be_bytes());
+    request.extend_from_slice(&transaction_id.to_be_bytes());
+    request.extend_from_slice(info_hash);
+    request.extend_from_slice(peer_id);
+    request.extend_from_slice(&0u64.to_be_bytes()); // downloaded
+    request.extend_from_slice(&(left as u64).to_be_bytes()); // left
+    request.extend_from_slice(&0u64.to_be_bytes()); // uploaded
+    request.extend_from_slice(&0u32.to_be_bytes()); // event
+    request.extend_from_slice(&0u32.to_be_bytes()); // ip
+    request.extend_from_slice(&key.to_be_bytes()); // key
+    request.extend_from_slice(&(-1i32).to_be_bytes()); // num_want
+    request.extend_from_slice(&6881u16.to_be_bytes()); // port
+
+    let mut response = [0u8; 2048];
+    let size = send_with_retries(&socket, &request, &mut response).await?;
+    if size < 20 {
+        return Err(String::from("Announce response from tracker was too short"));
+    }
+
+    let action = u32::from_be_bytes(response[0..4].try_into().unwrap());
+    let res_transaction_id = u32::from_be_bytes(response[4..8].try_into().unwrap());
+    if action != ACTION_ANNOUNCE || res_transaction_id != transaction_id {
+        return Err(String::from("Received unexpected announce response from tracker"));
+    }
+
+    let interval = i32::from_be_bytes(response[8..12].try_into().unwrap());
+    let leechers = i32::from_be_bytes(response[12..16].try_into().unwrap());
+    let seeders = i32::from_be_bytes(response[16..20].try_into().unwrap());
+
+    let mut peers = vec![];
+    for chunk in response[20..size].chunks(6) {
+        if chunk.len() < 6 {
+            break;
+        }
+        let ip: [u8; 4] = chunk[..4].try_into().unwrap();
+        let port = u16::from_be_bytes(chunk[4..6].try_into().unwrap());
+        peers.push(Peer {
+            id: String::from(""),
+            ip: std::net::IpAddr::from(ip).to_string(),
+            port: port.to_string(),
+        });
+    }
+
+    Ok(TrackerResponse {
+        failure_reason: None,
+        warning_message: None,
+        interval,
+        min_interval: None,
+        tracker_id: String::from(""),
+        complete: seeders,
+        incomplete: leechers,
+        peers,
+    })
+}
+
+/// Decodes a `%XX`-encoded string back into raw bytes.
+pub fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len() / 3);
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let Ok(byte) = u8::from_str_radix(&s[i + 1..i + 3], 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    decoded
+}